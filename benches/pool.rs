@@ -0,0 +1,135 @@
+// simple wall-clock benchmarks, run with `cargo bench`. no harness dependency so they build on stable.
+use rpool::{ Pool, PoolScaleMode, Poolable };
+use std::sync::Arc;
+use std::thread;
+use std::time::{ Duration, Instant };
+use std::hint::black_box;
+
+struct BenchItem {
+    value: u64,
+}
+
+impl Poolable<()> for BenchItem {
+    fn new(_context: &()) -> BenchItem {
+        BenchItem {
+            value: 0,
+        }
+    }
+
+    fn reset(&mut self) -> bool {
+        true
+    }
+}
+
+// an item that takes a while to construct, like a connection
+struct SlowItem;
+
+impl Poolable<()> for SlowItem {
+    fn new(_context: &()) -> SlowItem {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_micros(20) {}
+        SlowItem
+    }
+
+    fn reset(&mut self) -> bool {
+        true
+    }
+}
+
+fn run<F: Fn() -> Duration>(name: &str, f: F) {
+    // warm up once, then report the best of a few runs to filter out scheduling noise
+    f();
+    let best = (0..5).map(|_| f()).min().unwrap();
+    println!("{:<40} {:>10.2?}", name, best);
+}
+
+fn get_return_threaded(threads: usize, iterations: usize) -> Duration {
+    let pool: Arc<Pool<(), BenchItem>> = Pool::new(PoolScaleMode::Static { count: threads * 2 }, ());
+    let start = Instant::now();
+    let handles: Vec<_> = (0..threads).map(|_| {
+        let pool = pool.clone();
+        thread::spawn(move || {
+            for _ in 0..iterations {
+                let mut item = pool.get().unwrap();
+                item.value = item.value.wrapping_add(1);
+            }
+        })
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    start.elapsed()
+}
+
+// like `get_return_threaded` on one thread, but through `OwnedPoolItem`, which holds a `Weak` to the pool and upgrades it on drop
+fn get_return_owned(iterations: usize) -> Duration {
+    let pool: Arc<Pool<(), BenchItem>> = Pool::new(PoolScaleMode::Static { count: 2 }, ());
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let mut item = pool.get_owned().unwrap();
+        item.value = item.value.wrapping_add(1);
+    }
+    start.elapsed()
+}
+
+// just the refcount traffic a guard would cause per get/return: cloning and dropping an `Arc`, or cloning a `Weak` and upgrading it on drop
+fn refcount_traffic(iterations: usize, weak: bool) -> Duration {
+    let pool: Arc<Pool<(), BenchItem>> = Pool::new(PoolScaleMode::Static { count: 1 }, ());
+    let weak_pool = Arc::downgrade(&pool);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        if weak {
+            let guard_ref = black_box(weak_pool.clone());
+            drop(black_box(guard_ref.upgrade()));
+        } else {
+            drop(black_box(pool.clone()));
+        }
+    }
+    start.elapsed()
+}
+
+// `get` alone on a single thread: each round checks out every item, and only the gets count towards the time
+fn get_uncontended(items: usize, rounds: usize) -> Duration {
+    let pool: Arc<Pool<(), BenchItem>> = Pool::new(PoolScaleMode::Static { count: items }, ());
+    let mut held = Vec::with_capacity(items);
+    let mut elapsed = Duration::default();
+    for _ in 0..rounds {
+        let start = Instant::now();
+        for _ in 0..items {
+            held.push(pool.get().unwrap());
+        }
+        elapsed += start.elapsed();
+        held.clear();
+    }
+    elapsed
+}
+
+// slowest single `get` while `threads` threads each check out `per_thread` items from an empty pool growing in large chunks
+fn slowest_get_growing(threads: usize, per_thread: usize) -> Duration {
+    let pool: Arc<Pool<(), SlowItem>> = Pool::new(PoolScaleMode::AutoScale { maximum: None, initial: 0, chunk_size: threads * per_thread }, ());
+    let handles: Vec<_> = (0..threads).map(|_| {
+        let pool = pool.clone();
+        thread::spawn(move || {
+            let mut held = Vec::with_capacity(per_thread);
+            let mut slowest = Duration::default();
+            for _ in 0..per_thread {
+                let start = Instant::now();
+                held.push(pool.get().unwrap());
+                slowest = slowest.max(start.elapsed());
+            }
+            slowest
+        })
+    }).collect();
+    handles.into_iter().map(|handle| handle.join().unwrap()).max().unwrap()
+}
+
+fn main() {
+    run("get/return, 1 thread x 1M", || get_return_threaded(1, 1_000_000));
+    run("get/return, 4 threads x 250k", || get_return_threaded(4, 250_000));
+    run("get/return, 8 threads x 125k", || get_return_threaded(8, 125_000));
+    run("owned get/return, 1 thread x 1M", || get_return_owned(1_000_000));
+    run("Arc clone + drop x 1M", || refcount_traffic(1_000_000, false));
+    run("Weak clone + upgrade + drop x 1M", || refcount_traffic(1_000_000, true));
+    run("uncontended get, 1k x 1k rounds", || get_uncontended(1_000, 1_000));
+    run("slowest get while growing, 4 threads x 64", || slowest_get_growing(4, 64));
+}
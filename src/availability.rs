@@ -0,0 +1,173 @@
+use super::{ Pool, Poolable };
+use std::sync::Mutex;
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::mpsc::{ channel, Receiver, Sender, TryIter };
+#[cfg(feature = "async")]
+use std::{ pin::Pin, sync::Arc, sync::mpsc::TryRecvError, task::{ Context, Poll, Waker } };
+
+/// Emitted by `Pool::availability_events` when the share of idle items crosses one of the pool's `PoolBuilder::availability_watermarks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AvailabilityEvent {
+    /// Fewer than the low watermark's share of the capacity is idle.
+    Low { available: usize, capacity: usize },
+    /// At least the high watermark's share of the capacity is idle again, after a `Low` event.
+    Recovered { available: usize, capacity: usize },
+}
+
+/// The events of one `Pool::availability_events` subscriber, in the order they happened.
+///
+/// Iterating blocks until the next event, and ends once the pool is dropped. `try_iter` only yields what was already emitted. With the `async` feature, it is also a `futures` `Stream` of the events, waiting for the next one without blocking. Being an `Iterator` too, its stream `next` has to be called as `StreamExt::next(&mut events)`.
+#[derive(Debug)]
+pub struct AvailabilityEvents {
+    receiver: Receiver<AvailabilityEvent>,
+    #[cfg(feature = "async")]
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl AvailabilityEvents {
+    /// The events emitted so far and not yet taken, without blocking.
+    pub fn try_iter(&self) -> TryIter<'_, AvailabilityEvent> {
+        self.receiver.try_iter()
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_receiver(&self) -> Poll<Option<AvailabilityEvent>> {
+        match self.receiver.try_recv() {
+            Ok(event) => Poll::Ready(Some(event)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+}
+
+impl Iterator for AvailabilityEvents {
+    type Item = AvailabilityEvent;
+
+    fn next(&mut self) -> Option<AvailabilityEvent> {
+        self.receiver.recv().ok()
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for AvailabilityEvents {
+    type Item = AvailabilityEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<AvailabilityEvent>> {
+        let events = self.get_mut();
+        if let Poll::Ready(event) = events.poll_receiver() {
+            return Poll::Ready(event);
+        }
+        *events.waker.lock().unwrap() = Some(cx.waker().clone());
+        // an event sent before the waker was registered didn't wake us
+        events.poll_receiver()
+    }
+}
+
+// one subscriber's end of the channel, waking the task polling it as a `Stream` on every event and once the pool is gone
+struct Subscriber {
+    // only None while dropping
+    sender: Option<Sender<AvailabilityEvent>>,
+    #[cfg(feature = "async")]
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl Subscriber {
+    // false if the subscriber is gone
+    fn send(&self, event: AvailabilityEvent) -> bool {
+        let sent = self.sender.as_ref().unwrap().send(event).is_ok();
+        #[cfg(feature = "async")]
+        self.wake();
+        sent
+    }
+
+    #[cfg(feature = "async")]
+    fn wake(&self) {
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        // disconnect first, so the woken stream sees that it ended
+        drop(self.sender.take());
+        self.wake();
+    }
+}
+
+// the watermarks as shares of capacity, and whether availability is currently below the low one
+pub(crate) struct Watermarks {
+    low: f64,
+    high: f64,
+    below: AtomicBool,
+    // also serializes the edges, so subscribers see them in order
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl Watermarks {
+    pub(crate) fn new(low: f64, high: f64) -> Watermarks {
+        assert!(0.0 <= low && low <= high, "availability watermarks must satisfy 0 <= low <= high");
+        Watermarks {
+            low,
+            high,
+            below: AtomicBool::new(false),
+            subscribers: Mutex::new(vec![]),
+        }
+    }
+
+    // the edge, if any, crossed at `available` out of `capacity`, given whether availability was below the low watermark
+    fn edge(&self, below: bool, available: usize, capacity: usize) -> Option<AvailabilityEvent> {
+        let share = available as f64;
+        if !below && share < self.low * capacity as f64 {
+            Some(AvailabilityEvent::Low { available, capacity })
+        } else if below && share >= self.high * capacity as f64 {
+            Some(AvailabilityEvent::Recovered { available, capacity })
+        } else {
+            None
+        }
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Pool<Y, T> {
+    /// Subscribes to the pool's `AvailabilityEvent`s, e.g. for an admission controller applying backpressure. Events are edge triggered: after a `Low`, the next one is always a `Recovered`, and the other way around.
+    ///
+    /// Only pools built with `PoolBuilder::availability_watermarks` emit events; for others, the iterator ends right away.
+    pub fn availability_events(&self) -> AvailabilityEvents {
+        let (sender, receiver) = channel();
+        #[cfg(feature = "async")]
+        let waker = Arc::new(Mutex::new(None));
+        if let Some(watermarks) = &self.watermarks {
+            watermarks.subscribers.lock().unwrap().push(Subscriber {
+                sender: Some(sender),
+                #[cfg(feature = "async")]
+                waker: waker.clone(),
+            });
+        }
+        AvailabilityEvents {
+            receiver,
+            #[cfg(feature = "async")]
+            waker,
+        }
+    }
+
+    // called after every checkout and return. only takes the lock when a watermark looks crossed
+    #[inline]
+    pub(crate) fn check_availability(&self) {
+        let watermarks = match &self.watermarks {
+            Some(watermarks) => watermarks,
+            None => return,
+        };
+        if watermarks.edge(watermarks.below.load(Ordering::Acquire), self.idle(), self.capacity()).is_none() {
+            return;
+        }
+        let mut subscribers = watermarks.subscribers.lock().unwrap();
+        // another thread may have crossed it back in the meantime
+        let below = watermarks.below.load(Ordering::Acquire);
+        if let Some(event) = watermarks.edge(below, self.idle(), self.capacity()) {
+            watermarks.below.store(!below, Ordering::Release);
+            subscribers.retain(|subscriber| subscriber.send(event));
+        }
+    }
+}
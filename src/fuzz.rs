@@ -0,0 +1,234 @@
+// randomized model-based test of the public `Pool` API.
+//
+// each seed picks a pool configuration and then plays a stream of get/return/discard/grow/drain operations from a few
+// simulated clients, checking the pool's counters against a model of what the clients hold after every step. the
+// operations are interleaved by the seeded generator rather than the OS scheduler, so any failure replays exactly from
+// its seed. `fuzz_threaded` runs the same streams on real threads, where only the end state can be checked.
+//
+// invariants checked:
+// * every item is counted by the pool exactly once: `len() + checked_out() == capacity()`
+// * `checked_out()` matches the guards the clients hold, and `total_gets` the successful gets they made
+// * `capacity()` never exceeds a bounded pool's `maximum`, and a static pool never grows
+// * every item constructed is dropped exactly once, at the latest when the pool is dropped (no leaked or double freed items)
+//
+// `RPOOL_FUZZ_SEEDS=n` runs seeds `0..n` instead of the default, `RPOOL_FUZZ_SEED=s` replays only seed `s`.
+
+use super::*;
+use std::sync::atomic::AtomicIsize;
+use std::thread;
+
+const DEFAULT_SEEDS: u64 = 64;
+const STEPS: usize = 1000;
+const CLIENTS: usize = 4;
+// unbounded pools growing by doubling would otherwise double on every `grow_background`, and can run away between drains
+const MAX_BACKGROUND_GROWTH: usize = 4096;
+
+// xorshift64*, seeded through splitmix64 so that small seeds still give well mixed streams
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Rng((z ^ (z >> 31)) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn seeds() -> Vec<u64> {
+    if let Ok(seed) = std::env::var("RPOOL_FUZZ_SEED") {
+        return vec![seed.parse().expect("RPOOL_FUZZ_SEED must be an integer")];
+    }
+    let count = match std::env::var("RPOOL_FUZZ_SEEDS") {
+        Ok(count) => count.parse().expect("RPOOL_FUZZ_SEEDS must be an integer"),
+        Err(_) if cfg!(miri) => 4,
+        Err(_) => DEFAULT_SEEDS,
+    };
+    (0..count).collect()
+}
+
+// counts live items so leaks and double drops show up
+#[derive(Default)]
+struct FuzzContext {
+    live: Arc<AtomicIsize>,
+}
+
+struct FuzzItem {
+    live: Arc<AtomicIsize>,
+    valid: bool,
+}
+
+impl Poolable<FuzzContext> for FuzzItem {
+    fn new(context: &FuzzContext) -> FuzzItem {
+        context.live.fetch_add(1, Ordering::SeqCst);
+        FuzzItem {
+            live: context.live.clone(),
+            valid: true,
+        }
+    }
+
+    fn reset(&mut self) -> bool {
+        self.valid
+    }
+}
+
+impl Drop for FuzzItem {
+    fn drop(&mut self) {
+        assert!(self.live.fetch_sub(1, Ordering::SeqCst) > 0, "item dropped twice");
+    }
+}
+
+fn scale_mode(rng: &mut Rng) -> PoolScaleMode {
+    match rng.below(4) {
+        0 => PoolScaleMode::Static { count: rng.below(8) + 1 },
+        1 => PoolScaleMode::AutoScale { maximum: Some(rng.below(16) + 1), initial: rng.below(4), chunk_size: rng.below(4) },
+        2 => PoolScaleMode::AutoScale { maximum: None, initial: rng.below(4), chunk_size: rng.below(3) },
+        _ => PoolScaleMode::AutoCap { headroom_factor: 1.0 + rng.below(3) as f64 / 2.0, window: Duration::from_millis(rng.below(3) as u64) },
+    }
+}
+
+fn build_pool(rng: &mut Rng) -> (Arc<Pool<FuzzContext, FuzzItem>>, Arc<AtomicIsize>) {
+    let context = FuzzContext::default();
+    let live = context.live.clone();
+    let pool = Pool::new(scale_mode(rng), context);
+    (pool, live)
+}
+
+#[derive(Default)]
+struct Model {
+    clients: Vec<Vec<PoolGuard<FuzzContext, FuzzItem>>>,
+    gets: u64,
+}
+
+impl Model {
+    fn held(&self) -> usize {
+        self.clients.iter().map(Vec::len).sum()
+    }
+}
+
+// plays one randomly chosen operation for one randomly chosen client
+fn step(rng: &mut Rng, pool: &Arc<Pool<FuzzContext, FuzzItem>>, model: &mut Model) {
+    let client = rng.below(model.clients.len());
+    let held = &mut model.clients[client];
+    match rng.below(10) {
+        0..=2 => if let Some(guard) = pool.get() {
+            held.push(guard);
+            model.gets += 1;
+        },
+        3 => if let Some(guard) = pool.get_ready() {
+            held.push(guard);
+            model.gets += 1;
+        },
+        4 => {
+            let got = pool.get_batch_into(held, rng.below(4));
+            model.gets += got as u64;
+        },
+        5 | 6 => if !held.is_empty() {
+            drop(held.swap_remove(rng.below(held.len())));
+        },
+        7 => if !held.is_empty() {
+            let mut guard = held.swap_remove(rng.below(held.len()));
+            guard.valid = rng.below(2) == 0;
+            let valid = guard.valid;
+            assert_eq!(guard.recycle().is_ok(), valid);
+        },
+        8 => if rng.below(2) == 0 {
+            if pool.capacity() < MAX_BACKGROUND_GROWTH {
+                pool.grow_background();
+            }
+        } else {
+            pool.reserve(rng.below(8));
+        },
+        _ => {
+            let drained = pool.drain();
+            let expected = drained.len();
+            assert_eq!(drained.count(), expected);
+        },
+    }
+}
+
+fn check(pool: &Pool<FuzzContext, FuzzItem>, live: &AtomicIsize, model: &Model) {
+    let capacity = pool.capacity();
+    assert_eq!(pool.checked_out(), model.held());
+    #[cfg(feature = "stats")]
+    assert_eq!(pool.len() + pool.checked_out(), capacity);
+    #[cfg(feature = "stats")]
+    assert_eq!(pool.metrics().total_gets, model.gets);
+    assert_eq!(live.load(Ordering::SeqCst), capacity as isize);
+    if let Some(limit) = pool.capacity_limit() {
+        assert!(capacity <= limit);
+    }
+}
+
+fn run_seed(seed: u64) {
+    let mut rng = Rng::new(seed);
+    let (pool, live) = build_pool(&mut rng);
+    let mut model = Model {
+        clients: (0..CLIENTS).map(|_| vec![]).collect(),
+        gets: 0,
+    };
+    let steps = if cfg!(miri) { STEPS / 10 } else { STEPS };
+    for _ in 0..steps {
+        step(&mut rng, &pool, &mut model);
+        check(&pool, &live, &model);
+    }
+    drop(model);
+    let pool = Arc::try_unwrap(pool).unwrap_or_else(|_| panic!("guards outlived the model"));
+    assert_eq!(pool.checked_out(), 0);
+    drop(pool);
+    assert_eq!(live.load(Ordering::SeqCst), 0, "items leaked");
+}
+
+#[test]
+fn fuzz_sequential() {
+    for seed in seeds() {
+        // a panic here reports the seed to replay with `RPOOL_FUZZ_SEED`
+        let result = std::panic::catch_unwind(|| run_seed(seed));
+        assert!(result.is_ok(), "fuzz_sequential failed for seed {}", seed);
+    }
+}
+
+#[test]
+fn fuzz_threaded() {
+    for seed in seeds() {
+        let mut rng = Rng::new(seed);
+        let (pool, live) = build_pool(&mut rng);
+        let handles: Vec<_> = (0..CLIENTS as u64).map(|client| {
+            let pool = pool.clone();
+            let mut rng = Rng::new(seed ^ (client + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            thread::spawn(move || {
+                let mut model = Model {
+                    clients: vec![vec![]],
+                    gets: 0,
+                };
+                let steps = if cfg!(miri) { STEPS / 10 } else { STEPS };
+                // a drain on another thread can only ever shrink the pool, so per-client invariants don't hold here
+                for _ in 0..steps {
+                    step(&mut rng, &pool, &mut model);
+                }
+                model.gets
+            })
+        }).collect();
+        #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+        let gets: u64 = handles.into_iter().map(|handle| handle.join().expect("fuzz client panicked")).sum();
+        assert_eq!(pool.checked_out(), 0, "seed {}", seed);
+        #[cfg(feature = "stats")]
+        assert_eq!(pool.len(), pool.capacity(), "seed {}", seed);
+        #[cfg(feature = "stats")]
+        assert_eq!(pool.metrics().total_gets, gets, "seed {}", seed);
+        assert_eq!(live.load(Ordering::SeqCst), pool.capacity() as isize, "seed {}", seed);
+        drop(pool);
+        assert_eq!(live.load(Ordering::SeqCst), 0, "items leaked for seed {}", seed);
+    }
+}
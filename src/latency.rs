@@ -0,0 +1,58 @@
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::time::Duration;
+
+/// Number of buckets in `Pool::latency_histogram`. Bucket `i` counts acquisitions taking `[2^i, 2^(i + 1))` microseconds, except that bucket 0 also holds anything faster and the last bucket anything slower.
+pub const LATENCY_BUCKETS: usize = 32;
+
+// one counter per power of two microseconds, so recording is a single relaxed increment
+pub(crate) struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS],
+}
+
+impl LatencyHistogram {
+    pub(crate) fn new() -> LatencyHistogram {
+        LatencyHistogram {
+            buckets: Default::default(),
+        }
+    }
+
+    pub(crate) fn bucket(duration: Duration) -> usize {
+        let micros = duration.as_micros();
+        if micros == 0 {
+            return 0;
+        }
+        let log2 = (u128::BITS - 1 - micros.leading_zeros()) as usize;
+        log2.min(LATENCY_BUCKETS - 1)
+    }
+
+    pub(crate) fn record(&self, duration: Duration) {
+        self.record_bucket(LatencyHistogram::bucket(duration));
+    }
+
+    pub(crate) fn record_bucket(&self, bucket: usize) {
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> [u64; LATENCY_BUCKETS] {
+        let mut snapshot = [0; LATENCY_BUCKETS];
+        for (count, bucket) in snapshot.iter_mut().zip(self.buckets.iter()) {
+            *count = bucket.load(Ordering::Relaxed);
+        }
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        assert_eq!(LatencyHistogram::bucket(Duration::from_nanos(500)), 0);
+        assert_eq!(LatencyHistogram::bucket(Duration::from_micros(1)), 0);
+        assert_eq!(LatencyHistogram::bucket(Duration::from_micros(3)), 1);
+        assert_eq!(LatencyHistogram::bucket(Duration::from_micros(1024)), 10);
+        assert_eq!(LatencyHistogram::bucket(Duration::from_millis(24)), 14);
+        assert_eq!(LatencyHistogram::bucket(Duration::from_secs(1 << 40)), LATENCY_BUCKETS - 1);
+    }
+}
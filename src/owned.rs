@@ -0,0 +1,184 @@
+use super::{ ItemNode, Pool, PoolGuard, Poolable };
+use std::sync::{ Arc, Weak, mpsc::{ sync_channel, Receiver, SyncSender, TryRecvError } };
+use std::ptr::NonNull;
+use std::ops::{ Deref, DerefMut };
+use std::fmt::{ Debug, Formatter, Result as FmtResult };
+
+/// A pooled item that, unlike `PoolGuard`, doesn't keep its pool alive: it only holds a `Weak` reference, and is simply dropped if the pool is gone by the time it is returned.
+///
+/// Returned by `Pool::get_owned` or `PoolGuard::into_owned`. Use `split` to lend the item to a `'static` closure and take it back afterwards.
+pub struct OwnedPoolItem<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> {
+    // owned exactly like a guard's node. once the pool is gone, the node is no longer in any of its lists, so we free it ourselves
+    data: Option<NonNull<ItemNode<T>>>,
+    pool: Weak<Pool<Y, T>>,
+    discard: bool,
+}
+
+unsafe impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Send for OwnedPoolItem<Y, T> {}
+unsafe impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Sync for OwnedPoolItem<Y, T> {}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> PoolGuard<Y, T> {
+    /// Converts the guard into an `OwnedPoolItem`, which no longer keeps the pool alive.
+    pub fn into_owned(mut self) -> OwnedPoolItem<Y, T> {
+        OwnedPoolItem {
+            data: self.data.take(),
+            pool: Arc::downgrade(&self.pool),
+            discard: self.discard,
+        }
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Pool<Y, T> {
+    /// Like `get`, but returns an `OwnedPoolItem`, which doesn't keep the pool alive.
+    pub fn get_owned(self: &Arc<Pool<Y, T>>) -> Option<OwnedPoolItem<Y, T>> {
+        self.get().map(PoolGuard::into_owned)
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> OwnedPoolItem<Y, T> {
+    /// Splits the item into a `Lent` half, which derefs to the item and can be moved into a `'static` closure or thread, and a `Reclaim` half to take the item back once the `Lent` half is dropped.
+    ///
+    /// If the `Reclaim` half is dropped first, the item returns to the pool when the `Lent` half is dropped.
+    pub fn split(self) -> Split<Y, T> {
+        let (sender, receiver) = sync_channel(1);
+        let node = self.data.unwrap();
+        (Lent {
+            item: Some(self),
+            sender,
+        }, Reclaim {
+            node,
+            receiver,
+        })
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Drop for OwnedPoolItem<Y, T> {
+    fn drop(&mut self) {
+        if let Some(node) = self.data.take() {
+            match self.pool.upgrade() {
+                Some(pool) => {
+                    pool.readd_node(node, self.discard);
+                },
+                None => {
+                    let mut node = unsafe { Box::from_raw(node.as_ptr()) };
+                    unsafe { node.item.assume_init_drop() };
+                },
+            }
+        }
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + Debug + 'static> Debug for OwnedPoolItem<Y, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self.data {
+            Some(_) => (**self).fmt(f),
+            None => write!(f, "expired pool item"),
+        }
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Deref for OwnedPoolItem<Y, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { ItemNode::item(self.data.unwrap()) }
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> DerefMut for OwnedPoolItem<Y, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { ItemNode::item_mut(self.data.unwrap()) }
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> AsRef<T> for OwnedPoolItem<Y, T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> AsMut<T> for OwnedPoolItem<Y, T> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+/// Both halves of a split `OwnedPoolItem`.
+pub type Split<Y, T> = (Lent<Y, T>, Reclaim<Y, T>);
+
+/// The half of a split `OwnedPoolItem` giving access to the item. Hands the item to its `Reclaim` half when dropped.
+pub struct Lent<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> {
+    // only None while being dropped or reunited
+    item: Option<OwnedPoolItem<Y, T>>,
+    sender: SyncSender<OwnedPoolItem<Y, T>>,
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Drop for Lent<Y, T> {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            // fails if the `Reclaim` half is gone, in which case the item is dropped back into the pool
+            let _ = self.sender.try_send(item);
+        }
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + Debug + 'static> Debug for Lent<Y, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.item {
+            Some(item) => item.fmt(f),
+            None => write!(f, "expired pool item"),
+        }
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Deref for Lent<Y, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.item.as_ref().unwrap()
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> DerefMut for Lent<Y, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.item.as_mut().unwrap()
+    }
+}
+
+/// The half of a split `OwnedPoolItem` waiting for the `Lent` half to be dropped.
+pub struct Reclaim<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> {
+    // identifies the matching `Lent` half in `reunite`, never dereferenced
+    node: NonNull<ItemNode<T>>,
+    receiver: Receiver<OwnedPoolItem<Y, T>>,
+}
+
+unsafe impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Send for Reclaim<Y, T> {}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Debug for Reclaim<Y, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "Reclaim")
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Reclaim<Y, T> {
+    /// Blocks until the `Lent` half is dropped and returns the item. Returns `None` if the `Lent` half was leaked with `mem::forget`.
+    pub fn reclaim(self) -> Option<OwnedPoolItem<Y, T>> {
+        self.receiver.recv().ok()
+    }
+
+    /// Returns the item if the `Lent` half has already been dropped, otherwise hands `self` back.
+    pub fn try_reclaim(self) -> Result<OwnedPoolItem<Y, T>, Reclaim<Y, T>> {
+        match self.receiver.try_recv() {
+            Ok(item) => Ok(item),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => Err(self),
+        }
+    }
+
+    /// Joins both halves back into the item without waiting, failing if they come from different `split`s.
+    pub fn reunite(self, mut lent: Lent<Y, T>) -> Result<OwnedPoolItem<Y, T>, Split<Y, T>> {
+        if lent.item.as_ref().and_then(|item| item.data) != Some(self.node) {
+            return Err((lent, self));
+        }
+        Ok(lent.item.take().unwrap())
+    }
+}
@@ -0,0 +1,106 @@
+use super::{ CheckoutRelease, Pool, PoolGuard, Poolable };
+use std::sync::Arc;
+use std::fmt::{ Debug, Formatter, Result as FmtResult };
+#[cfg(feature = "async")]
+use std::{ convert::Infallible, pin::Pin, task::{ Context, Poll } };
+
+/// Collects guards to return to their pool together, e.g. from a pipeline producing finished items, so parked getters are woken once per batch rather than once per item.
+///
+/// Returned by `Pool::return_sink`. Buffered guards stay checked out until `flush`, which also runs when the sink is dropped.
+///
+/// With the `async` feature, it is also a `futures` `Sink` of guards that never has to wait: `SinkExt::send_all` from a stream of guards returns each run of guards the stream has ready as one batch.
+pub struct ReturnSink<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> {
+    pool: Arc<Pool<Y, T>>,
+    guards: Vec<PoolGuard<Y, T>>,
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Pool<Y, T> {
+    /// Returns every guard in `guards` to the pool, as dropping them one by one would, but signals parked getters only once for the lot. Returns how many were returned.
+    ///
+    /// Guards of other pools are dropped as usual and not counted.
+    pub fn return_batch<I: IntoIterator<Item = PoolGuard<Y, T>>>(&self, guards: I) -> usize {
+        // also released if a recycle panics partway through the batch
+        let mut release = CheckoutRelease(self, 0);
+        for mut guard in guards {
+            if !std::ptr::eq(self, &*guard.pool) {
+                continue;
+            }
+            let node = guard.data.take().unwrap();
+            release.1 += 1;
+            self.recycle_node(node, guard.discard);
+        }
+        release.1
+    }
+
+    /// A `ReturnSink` buffering guards for `return_batch`.
+    pub fn return_sink(self: &Arc<Pool<Y, T>>) -> ReturnSink<Y, T> {
+        ReturnSink {
+            pool: self.clone(),
+            guards: vec![],
+        }
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> ReturnSink<Y, T> {
+    /// Buffers `guard` for the next `flush`. A guard from another pool is returned to it right away.
+    pub fn send(&mut self, guard: PoolGuard<Y, T>) {
+        if Arc::ptr_eq(&guard.pool, &self.pool) {
+            self.guards.push(guard);
+        }
+    }
+
+    /// Number of guards buffered since the last `flush`.
+    pub fn len(&self) -> usize {
+        self.guards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.guards.is_empty()
+    }
+
+    /// Returns every buffered guard to the pool as one batch, and how many there were.
+    pub fn flush(&mut self) -> usize {
+        self.pool.return_batch(self.guards.drain(..))
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Extend<PoolGuard<Y, T>> for ReturnSink<Y, T> {
+    fn extend<I: IntoIterator<Item = PoolGuard<Y, T>>>(&mut self, guards: I) {
+        guards.into_iter().for_each(|guard| self.send(guard));
+    }
+}
+
+#[cfg(feature = "async")]
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> futures_sink::Sink<PoolGuard<Y, T>> for ReturnSink<Y, T> {
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, guard: PoolGuard<Y, T>) -> Result<(), Infallible> {
+        self.get_mut().send(guard);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        self.get_mut().flush();
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Drop for ReturnSink<Y, T> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Debug for ReturnSink<Y, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "ReturnSink({} buffered)", self.guards.len())
+    }
+}
@@ -0,0 +1,90 @@
+use super::{ ItemNode, Pool, PoolGuard, Poolable };
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::ptr::NonNull;
+use std::fmt::{ Debug, Formatter, Result as FmtResult };
+
+/// A claim on one checkout slot of a pool, redeemed for an item with `redeem`. Dropping it unredeemed gives the slot back.
+///
+/// Returned by `Pool::try_reserve_slot`. The slot counts as checked out from the moment it is reserved.
+pub struct SlotToken<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> {
+    pool: Arc<Pool<Y, T>>,
+    slot: Slot<T>,
+}
+
+enum Slot<T> {
+    // an idle item set aside for the token, owned like a guard's node
+    Idle(NonNull<ItemNode<T>>),
+    // a slot of capacity claimed for growth, built on `redeem`
+    Growth,
+    Redeemed,
+}
+
+unsafe impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Send for SlotToken<Y, T> {}
+unsafe impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Sync for SlotToken<Y, T> {}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Pool<Y, T> {
+    /// Claims a checkout slot without handing out an item yet, or returns `None` wherever `get` would.
+    ///
+    /// The slot is backed by either an idle item or capacity the pool may grow into, so `SlotToken::redeem` always succeeds; if a new item has to be built, that happens in `redeem`.
+    pub fn try_reserve_slot(self: &Arc<Pool<Y, T>>) -> Option<SlotToken<Y, T>> {
+        if self.is_shutdown() {
+            return None;
+        }
+        let slot = loop {
+            if let Some(node) = self.pop_node() {
+                if self.is_stale(node) {
+                    self.refresh_node(node);
+                }
+                break Slot::Idle(node);
+            }
+            match self.claim_growth() {
+                Some(true) => break Slot::Growth,
+                Some(false) => (),
+                None => {
+                    self.report_exhausted();
+                    return None;
+                },
+            }
+        };
+        self.claim_checkout();
+        Some(SlotToken {
+            pool: self.clone(),
+            slot,
+        })
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> SlotToken<Y, T> {
+    /// Exchanges the token for an item, building it first if the slot was reserved by growing the pool.
+    pub fn redeem(mut self) -> PoolGuard<Y, T> {
+        let node = match self.slot {
+            Slot::Idle(node) => node,
+            // if this panics, the token still holds the slot and gives it back when dropped
+            Slot::Growth => self.pool.new_node(),
+            Slot::Redeemed => unreachable!("slot token redeemed twice"),
+        };
+        self.slot = Slot::Redeemed;
+        self.pool.guard(node)
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Drop for SlotToken<Y, T> {
+    fn drop(&mut self) {
+        match self.slot {
+            // never handed out, so there is nothing to reset
+            Slot::Idle(node) => self.pool.push_node(node),
+            Slot::Growth => {
+                self.pool.capacity.fetch_sub(1, Ordering::AcqRel);
+            },
+            Slot::Redeemed => return,
+        }
+        self.pool.release_checkout();
+    }
+}
+
+impl<Y: Send + Sync + 'static, T: Poolable<Y> + 'static> Debug for SlotToken<Y, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "SlotToken")
+    }
+}